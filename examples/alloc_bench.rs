@@ -0,0 +1,99 @@
+//! Count the heap allocations of decoding candidate words with `apply`, which
+//! returns a fresh `Vec` per call, against `apply_into` with one reused buffer,
+//! the way the solver validates candidates, and of a real search pass.
+//!
+//! Run with `cargo run --release --example alloc_bench`. Decoding every word of
+//! the sample message under 26 keys costs one allocation per decode with `apply`
+//! (338) and only the buffer's growth with `apply_into` (2). A pass of `crack`
+//! makes about 3 allocations per prefix, where calling `apply` per word would
+//! make one per word checked, up to 338 per prefix.
+
+use crack_ceasar::{crack, permutation_count, tokenize, CrackOptions, Dictionary, Mapping};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// The system allocator, counting calls to alloc.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// Allocations made and time taken by f.
+fn measure(f: impl FnOnce()) -> (usize, std::time::Duration) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    f();
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - before,
+        start.elapsed(),
+    )
+}
+
+fn main() {
+    let ciper = b"PRCSOFQX FP QDR AFOPQ CZSPR LA JFPALOQSKR QDFP FP ZK LIU BROJZK MOLTROE";
+    let words = tokenize(ciper);
+    let keys = (0..26)
+        .map(|shift| {
+            (0..26).fold(Mapping::default(), |mapping, l| {
+                mapping.set(b'A' + (l + shift) % 26, b'a' + l).unwrap()
+            })
+        })
+        .collect::<Vec<_>>();
+    let decodes = keys.len() * words.len();
+
+    let (apply_allocs, apply_time) = measure(|| {
+        for key in &keys {
+            for word in &words {
+                std::hint::black_box(key.apply(word));
+            }
+        }
+    });
+    let (into_allocs, into_time) = measure(|| {
+        let mut buf = Vec::new();
+        for key in &keys {
+            for word in &words {
+                key.apply_into(word, &mut buf);
+                std::hint::black_box(&buf);
+            }
+        }
+    });
+    println!("{} word decodes", decodes);
+    println!(
+        "  apply:      {} allocations, {:?}",
+        apply_allocs, apply_time
+    );
+    println!("  apply_into: {} allocations, {:?}", into_allocs, into_time);
+
+    // Prefix length 2 of the sample message: a full pass that finds nothing.
+    let dictionary = Dictionary::embedded(11);
+    let options = CrackOptions {
+        start_prefix_length: 2,
+        max_prefix_length: 2,
+        ..CrackOptions::default()
+    };
+    let (pass_allocs, pass_time) = measure(|| {
+        let _ = crack(ciper, std::slice::from_ref(&dictionary), &options);
+    });
+    let prefixes = permutation_count(2);
+    println!(
+        "one crack pass (prefix length 2, {} prefixes): {} allocations ({:.1} per prefix), {:?}",
+        prefixes,
+        pass_allocs,
+        pass_allocs as f64 / prefixes as f64,
+        pass_time
+    );
+}
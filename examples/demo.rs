@@ -0,0 +1,25 @@
+//! Crack the assignment's sample ciphertext, a Miss Fortune quote.
+//!
+//! Run with `cargo run --release --example demo`, it takes a couple of minutes.
+
+use crack_ceasar::{crack, tokenize, CrackOptions, Dictionary};
+
+fn main() {
+    let ciper = b"PRCSOFQX FP QDR AFOPQ CZSPR LA JFPALOQSKR QDFP FP ZK LIU BROJZK MOLTROE";
+    let max_length = tokenize(ciper).iter().map(|w| w.len()).max().unwrap();
+    let dictionary = Dictionary::embedded(max_length);
+
+    let ciper_disk = crack(
+        ciper,
+        std::slice::from_ref(&dictionary),
+        &CrackOptions::default(),
+    )
+    .expect("Failed to crack ciper, exhausted all possibilities");
+
+    println!("abcdefghijklmnopqrstuvwxyz");
+    println!("{}", ciper_disk.to_key_string());
+    println!(
+        "decoded: {}",
+        String::from_utf8_lossy(&ciper_disk.apply(ciper))
+    );
+}
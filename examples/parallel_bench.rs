@@ -0,0 +1,44 @@
+//! Compare `Mapping::apply` with `Mapping::apply_parallel` on inputs from a few
+//! kilobytes to tens of megabytes.
+//!
+//! Run with `cargo run --release --example parallel_bench`. It prints the size
+//! of the rayon pool first: the parallel version can only win on several cores,
+//! and on one core (the only setup measured so far) both run about as fast at
+//! every size, within a few percent.
+
+use crack_ceasar::Mapping;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 5;
+
+// Mean time of f over RUNS runs.
+fn mean_time(mut f: impl FnMut() -> Vec<u8>) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        std::hint::black_box(f());
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let mapping = Mapping::from_key_string("qwertyuiopasdfghjklzxcvbnm").unwrap();
+    let sentence = b"PRCSOFQX FP QDR AFOPQ CZSPR LA JFPALOQSKR QDFP FP ZK LIU BROJZK MOLTROE\n";
+    println!("rayon threads: {}", rayon::current_num_threads());
+    for size in [4 << 10, 1 << 20, 64 << 20] {
+        let ciper = sentence
+            .iter()
+            .copied()
+            .cycle()
+            .take(size)
+            .collect::<Vec<_>>();
+        assert_eq!(mapping.apply(&ciper), mapping.apply_parallel(&ciper));
+        let serial = mean_time(|| mapping.apply(&ciper));
+        let parallel = mean_time(|| mapping.apply_parallel(&ciper));
+        println!(
+            "{:>6} KiB  apply {:>14?}  apply_parallel {:>14?}",
+            size >> 10,
+            serial,
+            parallel
+        );
+    }
+}
@@ -0,0 +1,21 @@
+//! Loading word lists never panics, and every word kept can be looked up.
+
+#![no_main]
+
+use crack_ceasar::{Dictionary, WeightedDictionary};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_LENGTH: usize = 16;
+
+fuzz_target!(|list: &str| {
+    let words = || list.lines().map(String::from);
+    let dictionary = Dictionary::from_iter(words(), MAX_LENGTH);
+    let weighted = WeightedDictionary::from_ranked(words(), MAX_LENGTH);
+    for word in words() {
+        let word = word.trim().to_ascii_lowercase();
+        let kept = !word.is_empty() && word.len() <= MAX_LENGTH;
+        assert_eq!(dictionary.contains(word.as_bytes()), kept);
+        assert_eq!(weighted.rank(word.as_bytes()).is_some(), kept);
+        assert_eq!(weighted.dictionary().contains(word.as_bytes()), kept);
+    }
+});
@@ -0,0 +1,13 @@
+//! `Mapping::from_bytes` never panics, and keys it accepts survive a trip
+//! through `to_bytes`.
+
+#![no_main]
+
+use crack_ceasar::Mapping;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|bytes: [u8; 26]| {
+    if let Ok(mapping) = Mapping::from_bytes(&bytes) {
+        assert_eq!(mapping.to_bytes(), bytes);
+    }
+});
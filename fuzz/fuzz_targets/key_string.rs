@@ -0,0 +1,16 @@
+//! `Mapping::from_key_string` never panics, and keys it accepts survive a trip
+//! through `to_key_string`.
+
+#![no_main]
+
+use crack_ceasar::{decode_with_key, Mapping};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|key: &str| {
+    if let Ok(mapping) = Mapping::from_key_string(key) {
+        let reparsed = Mapping::from_key_string(&mapping.to_key_string()).unwrap();
+        assert_eq!(reparsed, mapping);
+        assert!(key.eq_ignore_ascii_case(&mapping.to_key_string()));
+        decode_with_key(key.as_bytes(), key).unwrap();
+    }
+});
@@ -0,0 +1,12 @@
+//! `decode_numeric` never panics, and decoding its output again changes nothing:
+//! the letters it writes are never read as numbers.
+
+#![no_main]
+
+use crack_ceasar::decode_numeric;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| {
+    let decoded = decode_numeric(input);
+    assert_eq!(decode_numeric(&decoded), decoded);
+});
@@ -1,141 +1,600 @@
-use itertools::Itertools;
-use rayon::prelude::*;
-use std::collections::HashSet;
-
-/// Represent a mapping from encoded letter to decoded letter.
-#[derive(Clone, Default)]
-struct Mapping {
-    map: [Option<u8>; 26], // index map encoded letter to decoded letter
-    members: u32,          // bitset of all decoded letters that are mapped
+use crack_ceasar::{
+    crack_all, crack_annealing, crack_detailed, crack_with_crib, decode_numeric, diff_decodes,
+    index_of_coincidence, nearest_word, parse_cribs, permutation_count, strip_framing, tokenize,
+    top_words, ungroup, unmatchable_words, CommonWordScorer, CoolingSchedule, CrackError,
+    CrackOptions, Diagnostics, Dictionary, Fill, Framed, LongWordScorer, Mapping, Progress,
+    TrigramScorer, WeightedDictionary, DEFAULT_FRAME_MARKERS,
+};
+use std::io::{IsTerminal, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: crack_ceasar [OPTIONS] <CIPHERTEXT>...
+       crack_ceasar [OPTIONS] --file <PATH>
+       crack_ceasar [OPTIONS] -
+
+Crack a monoalphabetic substitution cipher written in uppercase letters.
+Pass the ciphertext as arguments, read it from a file, or use `-` to read stdin.
+
+Options:
+  -f, --file <PATH>  Read the ciphertext from PATH
+  --cribs <PATH>     Known letter mappings, one per line like Q=t; the rest of the
+                     key is then searched word by word, not keyword-style
+  --min-valid-ratio <R>
+                     Accept keys under which a fraction R of the words are valid
+  --fill <MODE>      What letters absent from the ciphertext decode to in the key:
+                     arbitrary (default), identity or unmapped
+  --truncated        Accept a last word cut off mid-word
+  --grouped          The ciphertext is written in letter groups (WKHPD QZDV), so
+                     word boundaries are lost; it is cracked by simulated annealing
+                     on letter trigrams instead, which needs a few hundred letters
+                     and usually leaves a few letters wrong
+  --numeric          The ciphertext is numbers 1-26 standing for letters, dashes
+                     between letters and spaces between words (8-9 20-8-5-18-5)
+  --all              Show every key found at the first successful prefix length,
+                     marking where their decodes differ from the best one
+  --max-candidates <N>
+                     Keep at most N keys with --all, --ranked-words or
+                     --candidates-out
+  --top-words <N>    After cracking, list the N most frequent decoded words
+  --ranked-words <PATH>
+                     Rank the keys found by how common their decoded words are,
+                     from a word list in PATH, most common word first
+  --candidates-out <PATH>
+                     Write every key found at the first successful prefix length
+                     to PATH, best first, one per line with the first decoded line
+  --strip-framing    Set aside leading and trailing marker lines such as
+                     BEGIN MESSAGE / END MESSAGE, shown undecoded in the output
+  --frame-marker <LINE>
+                     Also treat LINE as a marker line, implies --strip-framing
+  --stats            Print the time spent on each prefix length (not with --all,
+                     --ranked-words or --candidates-out)
+  -q, --quiet        Only print the decoded text, nothing on failure
+  -h, --help         Print this help
+
+Exit status:
+  0  cracked
+  1  no key found, every candidate was rejected
+  2  invalid arguments or unreadable input
+  3  the input doesn't look like a substitution cipher (too few letters)
+  4  failed to write the --candidates-out file
+  5  the input has no letters at all";
+
+// Exit status for each way cracking can fail, see USAGE.
+fn exit_code(err: &CrackError) -> i32 {
+    match err {
+        CrackError::Exhausted(_) => 1,
+        CrackError::NotAlphabetic(_) => 3,
+        CrackError::NoAlphabetic => 5,
+    }
+}
+const EXIT_USAGE: i32 = 2;
+const EXIT_WRITE: i32 = 4;
+
+// Longest dictionary word considered when the last word may be truncated.
+const TRUNCATED_MAX_LENGTH: usize = 32;
+
+// Where to read the ciphertext from.
+enum Input {
+    Args(Vec<String>),
+    File(String),
+    Stdin,
+}
+
+struct Args {
+    input: Input,
+    options: CrackOptions,
+    all: bool,
+    quiet: bool,
+    grouped: bool,
+    numeric: bool,
+    frame_markers: Option<Vec<String>>,
+    top_words: Option<usize>,
+    candidates_out: Option<String>,
+    ranked_words: Option<String>,
+    cribs: Option<String>,
 }
 
-impl Mapping {
-    pub fn get(&self, c: u8) -> Option<u8> {
-        self.map[(c - b'A') as usize]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut words = Vec::new();
+    let mut input = None;
+    let mut options = CrackOptions::default();
+    let mut all = false;
+    let mut quiet = false;
+    let mut grouped = false;
+    let mut numeric = false;
+    let mut frame_markers: Option<Vec<String>> = None;
+    let mut top_words = None;
+    let mut candidates_out = None;
+    let mut ranked_words = None;
+    let mut cribs = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-f" | "--file" => {
+                let path = args.next().ok_or("--file requires a path")?;
+                input = Some(Input::File(path));
+            }
+            "--cribs" => {
+                let path = args.next().ok_or("--cribs requires a path")?;
+                cribs = Some(path);
+            }
+            "--min-valid-ratio" => {
+                let r = args.next().ok_or("--min-valid-ratio requires a ratio")?;
+                options.min_valid_ratio = r.parse().map_err(|_| format!("invalid ratio {}", r))?;
+            }
+            "--fill" => {
+                options.fill = match args.next().as_deref() {
+                    Some("arbitrary") => Fill::Arbitrary,
+                    Some("identity") => Fill::Identity,
+                    Some("unmapped") => Fill::Unmapped,
+                    _ => return Err("--fill requires arbitrary, identity or unmapped".into()),
+                };
+            }
+            "--all" => all = true,
+            "-q" | "--quiet" => quiet = true,
+            "--truncated" => options.last_word_prefix = true,
+            "--stats" => options.collect_stats = true,
+            "--grouped" => grouped = true,
+            "--numeric" => numeric = true,
+            "--strip-framing" => {
+                frame_markers.get_or_insert_with(Vec::new);
+            }
+            "--frame-marker" => {
+                let marker = args.next().ok_or("--frame-marker requires a line")?;
+                frame_markers.get_or_insert_with(Vec::new).push(marker);
+            }
+            "--max-candidates" => {
+                let n = args.next().ok_or("--max-candidates requires a count")?;
+                let n = n.parse().map_err(|_| format!("invalid count {}", n))?;
+                if n == 0 {
+                    return Err("--max-candidates must be at least 1".into());
+                }
+                options.max_candidates = Some(n);
+            }
+            "--top-words" => {
+                let n = args.next().ok_or("--top-words requires a count")?;
+                let n = n.parse().map_err(|_| format!("invalid count {}", n))?;
+                top_words = Some(n);
+            }
+            "--candidates-out" => {
+                let path = args.next().ok_or("--candidates-out requires a path")?;
+                candidates_out = Some(path);
+            }
+            "--ranked-words" => {
+                let path = args.next().ok_or("--ranked-words requires a path")?;
+                ranked_words = Some(path);
+            }
+            "-" => input = Some(Input::Stdin),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ => words.push(arg),
+        }
     }
 
-    pub fn set(&self, c: u8, l: u8) -> Result<Mapping, ()> {
-        let idx_c = (c - b'A') as usize;
+    let input = match input {
+        Some(_) if !words.is_empty() => {
+            return Err("give the ciphertext either as arguments or as input, not both".into())
+        }
+        Some(input) => input,
+        None if !words.is_empty() => Input::Args(words),
+        None => return Err("no ciphertext given".into()),
+    };
+    if grouped && (cribs.is_some() || all || candidates_out.is_some() || ranked_words.is_some()) {
+        return Err(
+            "--grouped can't be combined with --cribs, --all, --candidates-out or --ranked-words"
+                .into(),
+        );
+    }
+    // The crib search validates every word and returns one key, whatever the
+    // options say, so don't let them be silently ignored.
+    let defaults = CrackOptions::default();
+    let search_options = options.min_valid_ratio != defaults.min_valid_ratio
+        || options.last_word_prefix
+        || options.fill != defaults.fill
+        || options.max_candidates.is_some();
+    if cribs.is_some()
+        && (search_options || all || candidates_out.is_some() || ranked_words.is_some())
+    {
+        return Err(
+            "--cribs can't be combined with --min-valid-ratio, --truncated, --fill, \
+             --max-candidates, --all, --candidates-out or --ranked-words"
+                .into(),
+        );
+    }
+    Ok(Args {
+        input,
+        options,
+        all,
+        quiet,
+        grouped,
+        numeric,
+        frame_markers,
+        top_words,
+        candidates_out,
+        ranked_words,
+        cribs,
+    })
+}
 
-        if self.map[idx_c].is_none() || self.map[idx_c] == Some(l) {
-            let mut result = self.clone();
+fn read_input(input: &Input) -> std::io::Result<Vec<u8>> {
+    match input {
+        Input::Args(words) => Ok(words.join(" ").into_bytes()),
+        Input::File(path) => std::fs::read(path),
+        Input::Stdin => {
+            let mut buf = Vec::new();
+            std::io::stdin().read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+    }
+}
 
-            result.map[idx_c] = Some(l);
-            let idx_l = (l - b'a') as usize;
-            result.members |= 1 << idx_l;
+// Write one line per candidate: its key string and the first line of its decode.
+fn write_candidates(path: &str, ciper: &[u8], candidates: &[&Mapping]) -> std::io::Result<()> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for mapping in candidates {
+        let decoded = mapping.apply_marking_unknown(ciper);
+        let first_line = decoded.split(|&b| b == b'\n').next().unwrap_or_default();
+        writeln!(
+            out,
+            "{} {}",
+            mapping.to_key_string(),
+            String::from_utf8_lossy(first_line).trim_end()
+        )?;
+    }
+    out.flush()
+}
 
-            Ok(result)
-        } else {
-            Err(())
+// Renders search progress on stderr, keeping stdout for the results: a live status
+// line when stderr is a terminal, plain lines otherwise.
+struct Spinner {
+    live: bool,
+    state: Mutex<SpinnerState>,
+}
+
+#[derive(Default)]
+struct SpinnerState {
+    frame: usize,
+    prefix_length: usize,
+    tried: u128,
+    total: u128,
+    sample: Option<(Instant, u128)>, // last throughput measurement
+    rate: Option<f64>,               // prefixes per second, smoothed
+    drawn: Option<Instant>,
+}
+
+impl Spinner {
+    const FRAMES: &'static [char] = &['|', '/', '-', '\\'];
+    const REDRAW: Duration = Duration::from_millis(100);
+
+    fn new(live: bool) -> Self {
+        Spinner {
+            live,
+            state: Mutex::default(),
         }
     }
 
-    pub fn apply(&self, ciper: &[u8]) -> Vec<u8> {
-        ciper
-            .iter()
-            .map(|c| {
-                if c.is_ascii_uppercase() {
-                    self.get(*c).unwrap_or(*c)
-                } else {
-                    *c
+    fn update(&self, progress: &Progress) {
+        let mut state = self.state.lock().unwrap();
+        match *progress {
+            Progress::Pass { prefix_length } => {
+                if !self.live {
+                    eprintln!("{}", progress);
+                    return;
                 }
-            })
-            .collect()
+                *state = SpinnerState {
+                    frame: state.frame,
+                    prefix_length,
+                    total: permutation_count(prefix_length),
+                    sample: Some((Instant::now(), 0)),
+                    ..SpinnerState::default()
+                };
+                self.draw(&mut state);
+            }
+            Progress::Prefixes { tried, total, .. } if self.live => {
+                // Threads count and report separately, so an older count can
+                // arrive after a newer one. It has nothing new to show.
+                if tried < state.tried {
+                    return;
+                }
+                let now = Instant::now();
+                if let Some((then, before)) = state.sample {
+                    let elapsed = now.duration_since(then).as_secs_f64();
+                    if elapsed >= 0.5 {
+                        // Recent throughput counts most, the pass can speed up or slow down.
+                        let rate = (tried - before) as f64 / elapsed;
+                        state.rate = Some(state.rate.map_or(rate, |r| 0.7 * r + 0.3 * rate));
+                        state.sample = Some((now, tried));
+                    }
+                }
+                state.tried = tried;
+                state.total = total;
+                if state.drawn.is_none_or(|t| t.elapsed() >= Self::REDRAW) {
+                    self.draw(&mut state);
+                }
+            }
+            Progress::UnmatchableWord { .. } => {
+                if self.live {
+                    eprint!("\r\x1b[K");
+                }
+                eprintln!("warning: {}", progress);
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&self, state: &mut SpinnerState) {
+        state.frame = (state.frame + 1) % Self::FRAMES.len();
+        state.drawn = Some(Instant::now());
+        let percent = if state.total > 0 {
+            state.tried as f64 * 100.0 / state.total as f64
+        } else {
+            0.0
+        };
+        let eta = match state.rate {
+            Some(rate) if rate > 0.0 => {
+                let secs = (state.total - state.tried.min(state.total)) as f64 / rate;
+                format!(", ETA {}", format_duration(secs))
+            }
+            _ => String::new(),
+        };
+        eprint!(
+            "\r\x1b[K{} prefix length {}: {:.1}%, {} keys tried{}",
+            Self::FRAMES[state.frame],
+            state.prefix_length,
+            percent,
+            state.tried * 26,
+            eta
+        );
+    }
+
+    // Remove the status line before the results are printed.
+    fn clear(&self) {
+        if self.live && self.state.lock().unwrap().drawn.is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs / 60 % 60),
     }
 }
 
 fn main() {
-    let ciper = b"PRCSOFQX FP QDR AFOPQ CZSPR LA JFPALOQSKR QDFP FP ZK LIU BROJZK MOLTROE";
-    let max_length = ciper.split(|&b| b == b' ').map(|w| w.len()).max().unwrap();
-    let words_by_length = words_by_length(max_length);
+    let mut args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    let ciper = match read_input(&args.input) {
+        Ok(ciper) => ciper,
+        Err(err) => {
+            if !args.quiet {
+                eprintln!("error: failed to read ciphertext: {}", err);
+            }
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    let cribs = args.cribs.as_ref().map(|path| {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|err| {
+            if !args.quiet {
+                eprintln!("error: failed to read cribs from {}: {}", path, err);
+            }
+            std::process::exit(EXIT_USAGE);
+        });
+        parse_cribs(&text).unwrap_or_else(|errors| {
+            if !args.quiet {
+                for err in errors {
+                    eprintln!("error: {}: {}", path, err);
+                }
+            }
+            std::process::exit(EXIT_USAGE);
+        })
+    });
+    let spinner = Arc::new(Spinner::new(std::io::stderr().is_terminal()));
+    if !args.quiet {
+        let spinner = spinner.clone();
+        args.options.on_progress = Some(Arc::new(move |progress| spinner.update(progress)));
+    }
+    let ciper = if args.numeric {
+        decode_numeric(&ciper)
+    } else {
+        ciper
+    };
+    let framed = match &args.frame_markers {
+        Some(extra) => {
+            let markers = DEFAULT_FRAME_MARKERS
+                .iter()
+                .copied()
+                .chain(extra.iter().map(|m| m.as_bytes()))
+                .collect::<Vec<_>>();
+            strip_framing(&ciper, &markers)
+        }
+        None => Framed {
+            header: &[],
+            body: &ciper,
+            footer: &[],
+        },
+    };
+    let ciper = framed.body;
+
+    // Empty input has no words, cracking it fails cleanly below.
+    let mut max_length = tokenize(ciper).iter().map(|w| w.len()).max().unwrap_or(0);
+    if args.options.last_word_prefix {
+        // The cut off word may be the start of a longer one.
+        max_length = max_length.max(TRUNCATED_MAX_LENGTH);
+    }
+    let dictionary = Dictionary::embedded(max_length);
+    let ranked = match &args.ranked_words {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(list) => Some(WeightedDictionary::from_ranked(
+                list.lines().map(String::from),
+                max_length,
+            )),
+            Err(err) => {
+                if !args.quiet {
+                    eprintln!("error: failed to read ranked words from {}: {}", path, err);
+                }
+                std::process::exit(EXIT_USAGE);
+            }
+        },
+        None => None,
+    };
+
+    let dictionaries = std::slice::from_ref(&dictionary);
+    let mut pass_times = Vec::new();
+    let result = if !ciper.iter().any(u8::is_ascii_uppercase) {
+        Err(CrackError::NoAlphabetic)
+    } else if args.grouped {
+        let letters = ungroup(ciper);
+        let scorer = TrigramScorer::embedded();
+        Ok(vec![crack_annealing(
+            &letters,
+            &scorer,
+            &CoolingSchedule::default(),
+        )])
+    } else if let Some(cribs) = &cribs {
+        crack_with_crib(ciper, &dictionary, cribs)
+            .map(|mapping| vec![mapping])
+            .ok_or_else(|| {
+                CrackError::Exhausted(Diagnostics {
+                    unmatchable_words: unmatchable_words(ciper, dictionaries, &args.options).len(),
+                    index_of_coincidence: index_of_coincidence(ciper),
+                    prefix_cap_hit: false,
+                })
+            })
+    } else if args.all || args.candidates_out.is_some() || ranked.is_some() {
+        match &ranked {
+            Some(ranked) => {
+                let scorer = CommonWordScorer { dictionary: ranked };
+                crack_all(ciper, dictionaries, &args.options, &scorer)
+            }
+            None => {
+                let scorer = LongWordScorer {
+                    dictionary: &dictionary,
+                };
+                crack_all(ciper, dictionaries, &args.options, &scorer)
+            }
+        }
+    } else {
+        crack_detailed(ciper, dictionaries, &args.options).map(|result| {
+            pass_times = result.pass_times;
+            vec![result.mapping]
+        })
+    };
+    spinner.clear();
+    let (ciper_disk, others) = match result {
+        Ok(mut mappings) => {
+            let best = mappings.remove(0);
+            (best, mappings)
+        }
+        Err(err) => {
+            if !args.quiet {
+                eprintln!("Failed to crack ciper: {}", err);
+            }
+            std::process::exit(exit_code(&err));
+        }
+    };
+
+    if let Some(path) = &args.candidates_out {
+        let candidates = std::iter::once(&ciper_disk)
+            .chain(&others)
+            .collect::<Vec<_>>();
+        if let Err(err) = write_candidates(path, ciper, &candidates) {
+            if !args.quiet {
+                eprintln!("error: failed to write candidates to {}: {}", path, err);
+            }
+            std::process::exit(EXIT_WRITE);
+        }
+    }
+    let others = if args.all { others } else { Vec::new() };
 
-    let ciper_disk =
-        crack(ciper, &words_by_length).expect("Failed to crack ciper, exhausted all possibilities");
+    if args.quiet {
+        let mut decoded = framed.reattach(&ciper_disk.apply_marking_unknown(ciper));
+        if decoded.last() != Some(&b'\n') {
+            decoded.push(b'\n');
+        }
+        // Nothing sensible to do if stdout is gone.
+        let _ = std::io::stdout().write_all(&decoded);
+        return;
+    }
 
     // Output
     println!("Result Found!");
 
-    let decoded = ciper_disk.apply(ciper);
+    let decoded = ciper_disk.apply_marking_unknown(ciper);
     println!("====================");
-    println!("abcedfghijklmnopqrstuvwxyz");
-    for i in 0..26 {
-        let c = ciper_disk.map.iter().position(|&x| x == Some(b'a' + i));
-        print!("{}", c.map_or('?', |x| (x as u8 + b'A') as char));
-    }
-    println!("\n");
+    println!("abcdefghijklmnopqrstuvwxyz");
+    println!("{}\n", ciper_disk.to_key_string());
 
-    println!("decoed: {}", String::from_utf8_lossy(&decoded));
+    println!(
+        "decoded: {}",
+        String::from_utf8_lossy(&framed.reattach(&decoded))
+    );
+    if let Some(shift) = ciper_disk.caesar_shift_in(ciper) {
+        println!("this was Caesar shift {} (ROT-{})", shift, shift);
+    }
     println!("====================");
-}
-
-// Get a dictionary of words, partitioned by length.
-fn words_by_length<'a>(max_length: usize) -> Vec<HashSet<&'a [u8]>> {
-    let mut words_by_length = vec![Vec::new(); max_length + 1];
-
-    let two_words: &[&[u8]] = &[
-        b"am", b"an", b"as", b"at", b"be", b"by", b"do", b"go", b"he", b"if", b"in", b"is", b"it",
-        b"me", b"my", b"no", b"of", b"on", b"or", b"so", b"to", b"up", b"us", b"we",
-    ];
-    let three_words: &[&[u8]] = &[
-        b"all", b"and", b"any", b"are", b"boy", b"but", b"can", b"day", b"did", b"for", b"get",
-        b"had", b"has", b"her", b"him", b"his", b"how", b"its", b"let", b"man", b"new", b"not",
-        b"now", b"old", b"one", b"our", b"out", b"put", b"say", b"see", b"she", b"the", b"too",
-        b"two", b"use", b"was", b"way", b"who", b"you",
-    ];
-
-    include_bytes!("../words.txt")
-        .split(|&c| c == b'\n' || c == b'\r')
-        .filter(|b| !b.is_empty() && b.len() <= max_length)
-        .for_each(|w| {
-            words_by_length[w.len()].push(w);
-        });
-    words_by_length[2] = two_words.to_vec();
-    words_by_length[3] = three_words.to_vec();
-    words_by_length
-        .iter()
-        .map(|ws| ws.iter().copied().collect::<HashSet<_>>())
-        .collect()
-}
 
-fn crack(ciper: &[u8], words_by_length: &[HashSet<&[u8]>]) -> Option<Mapping> {
-    let ciper_words = ciper.split(|&b| b == b' ').collect_vec();
-
-    for k in 0u8..26 {
-        println!("trying prefix of length = {}...", k);
-        let result = (0u8..26)
-            .permutations(k as usize)
-            .par_bridge()
-            .find_map_any(|prefix| {
-                let mut mapping = Mapping::default();
-                for (i, &l) in prefix.iter().enumerate() {
-                    mapping = mapping.set(b'A' + l, b'a' + i as u8).unwrap();
-                }
-
-                for offset in 0..26 {
-                    let mut fmapping = mapping.clone();
-                    let mut di = offset;
-                    for i in k..26 {
-                        while fmapping.map[di as usize].is_some() {
-                            di = (di + 1) % 26;
-                        }
-                        fmapping = fmapping.set(b'A' + di, b'a' + i).unwrap();
-                    }
+    // Letter groups aren't words, there is nothing to check.
+    let invalid_words = tokenize(&decoded)
+        .into_iter()
+        .filter(|w| !args.grouped && !dictionary.contains(w))
+        .collect::<Vec<_>>();
+    if !invalid_words.is_empty() {
+        println!("words not in dictionary:");
+        for word in invalid_words {
+            match nearest_word(word, &dictionary) {
+                Some((nearest, distance)) => println!(
+                    "  {} (closest: {}, distance {})",
+                    String::from_utf8_lossy(word),
+                    String::from_utf8_lossy(nearest),
+                    distance
+                ),
+                None => println!("  {}", String::from_utf8_lossy(word)),
+            }
+        }
+    }
 
-                    let valid_words = ciper_words
-                        .iter()
-                        .map(|x| fmapping.apply(x))
-                        .all(|cw| words_by_length[cw.len()].contains(cw.as_slice()));
-                    if valid_words {
-                        return Some(fmapping);
-                    }
+    if !others.is_empty() {
+        println!("{} other candidate(s), ^ marks differences:", others.len());
+        for other in others {
+            let other_decoded = other.apply_marking_unknown(ciper);
+            println!("{}", other.to_key_string());
+            println!("  {}", String::from_utf8_lossy(&other_decoded));
+            let diff = diff_decodes(&decoded, &other_decoded);
+            if !diff.is_empty() {
+                let mut marks = vec![b' '; other_decoded.len()];
+                for (i, _, _) in diff {
+                    marks[i] = b'^';
                 }
+                println!("  {}", String::from_utf8_lossy(&marks).trim_end());
+            }
+        }
+    }
 
-                return None;
-            });
+    if let Some(n) = args.top_words {
+        println!("most frequent words:");
+        for (word, count, valid) in top_words(&decoded, &dictionary, n) {
+            let validity = if valid { "" } else { " (not in dictionary)" };
+            println!("{:>6} {}{}", count, String::from_utf8_lossy(word), validity);
+        }
+    }
 
-        if let Some(ciper_disk) = result {
-            return Some(ciper_disk);
+    if !pass_times.is_empty() {
+        println!("time per prefix length:");
+        for (k, elapsed) in pass_times {
+            println!("{:>6} {:.3}s", k, elapsed.as_secs_f64());
         }
     }
-    return None;
 }